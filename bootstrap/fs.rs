@@ -2,12 +2,15 @@
 use event::NotifyFlag;
 use fuse::*;
 use libc;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsStr;
-use std::fs::{File, Metadata};
-use std::io::Read;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
 use std::os::unix::fs::*;
 use std::path::*;
+use tempdir::TempDir;
 use time::Timespec;
 use zip::ZipArchive;
 
@@ -18,6 +21,98 @@ const TTL: Timespec = Timespec {
     nsec: 0,
 };
 
+/// Total size, across all cached entries, that the decompressed content cache is allowed to hold
+/// before evicting least-recently-used entries.
+const CONTENT_CACHE_CAP: u64 = 64 * 1024 * 1024;
+
+/// Entries larger than this are spilled to a temp file instead of kept in memory.
+const MEMORY_CACHE_ENTRY_LIMIT: u64 = 1024 * 1024;
+
+/// The name of the zip entry holding captured extended attributes. See its format documentation
+/// on `appimage.rs`'s `XATTRS_ENTRY_NAME`.
+const XATTRS_ENTRY_NAME: &'static str = ".xattrs";
+
+/// The name of the zip entry holding the encoded update endpoint. See `appimage.rs`'s
+/// `UPDATE_INFORMATION_ENTRY_NAME` for the format.
+const UPDATE_INFORMATION_ENTRY_NAME: &'static str = ".update_information";
+
+/// Read a little-endian `u32` out of `data` at `*pos`, advancing `*pos` past it. Returns `None`
+/// (without advancing `*pos`) if fewer than 4 bytes remain.
+fn read_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+    if data.len() - *pos < 4 {
+        return None;
+    }
+
+    let value = (data[*pos] as u32)
+        | ((data[*pos + 1] as u32) << 8)
+        | ((data[*pos + 2] as u32) << 16)
+        | ((data[*pos + 3] as u32) << 24);
+    *pos += 4;
+    Some(value)
+}
+
+/// Read a length-prefixed byte string out of `data` at `*pos`, advancing `*pos` past it. Returns
+/// `None` (without advancing `*pos`) if the length prefix or the bytes it claims don't fit.
+fn read_bytes<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let start = *pos;
+    let len = read_u32(data, pos)? as usize;
+
+    if data.len() - *pos < len {
+        *pos = start;
+        return None;
+    }
+
+    let bytes = &data[*pos..*pos + len];
+    *pos += len;
+    Some(bytes)
+}
+
+/// Parse the contents of the `.xattrs` entry into a map of entry path to its xattrs. Malformed or
+/// truncated data (the entry is untrusted input) simply yields whatever records were parsed
+/// successfully before the first error, rather than panicking.
+fn parse_xattrs(data: &[u8]) -> HashMap<PathBuf, HashMap<String, Vec<u8>>> {
+    let mut xattrs = HashMap::new();
+    let mut pos = 0;
+
+    let record_count = match read_u32(data, &mut pos) {
+        Some(v) => v,
+        None => return xattrs,
+    };
+
+    for _ in 0..record_count {
+        let name = match read_bytes(data, &mut pos) {
+            Some(v) => String::from_utf8_lossy(v).into_owned(),
+            None => break,
+        };
+        let xattr_count = match read_u32(data, &mut pos) {
+            Some(v) => v,
+            None => break,
+        };
+
+        let mut entry_xattrs = HashMap::new();
+        let mut truncated = false;
+        for _ in 0..xattr_count {
+            let xattr_name = match read_bytes(data, &mut pos) {
+                Some(v) => String::from_utf8_lossy(v).into_owned(),
+                None => { truncated = true; break; },
+            };
+            let xattr_value = match read_bytes(data, &mut pos) {
+                Some(v) => v.to_vec(),
+                None => { truncated = true; break; },
+            };
+            entry_xattrs.insert(xattr_name, xattr_value);
+        }
+
+        if truncated {
+            break;
+        }
+
+        xattrs.insert(PathBuf::from(name), entry_xattrs);
+    }
+
+    xattrs
+}
+
 /// Inode data type.
 type Inode = u64;
 
@@ -25,8 +120,13 @@ type Inode = u64;
 #[derive(Clone, Debug)]
 struct NodeData {
     path: PathBuf,
-    is_dir: bool,
     attr: FileAttr,
+
+    /// The inode of the containing directory. The root directory is its own parent.
+    parent_inode: Inode,
+
+    /// For symlinks, the raw bytes of the link target.
+    link_target: Option<Vec<u8>>,
 }
 
 impl NodeData {
@@ -39,6 +139,31 @@ impl NodeData {
     }
 }
 
+/// A fully decompressed entry, held either in memory or spilled to a temp file on disk.
+enum CacheData {
+    Memory(Vec<u8>),
+    Disk(File),
+}
+
+/// A cached decompressed entry and its size, tracked for the purposes of the cache's byte cap.
+struct CachedEntry {
+    data: CacheData,
+    size: u64,
+}
+
+/// Map a raw unix mode's file type bits to the corresponding FUSE file type.
+fn file_type_from_mode(mode: u32) -> FileType {
+    match mode & libc::S_IFMT {
+        libc::S_IFLNK => FileType::Symlink,
+        libc::S_IFIFO => FileType::NamedPipe,
+        libc::S_IFCHR => FileType::CharDevice,
+        libc::S_IFBLK => FileType::BlockDevice,
+        libc::S_IFSOCK => FileType::Socket,
+        libc::S_IFDIR => FileType::Directory,
+        _ => FileType::RegularFile,
+    }
+}
+
 pub struct AppImageFileSystem {
     /// Metadata about the AppImage file.
     metadata: Metadata,
@@ -49,11 +174,33 @@ pub struct AppImageFileSystem {
     /// An open handle to the zipped AppImage filesystem.
     archive: ZipArchive<File>,
 
-    /// Cache of inode data.
+    /// Cache of inode data, populated up front for every real and synthesized node.
     inode_cache: HashMap<Inode, NodeData>,
 
-    /// Cache mapping paths to inodes.
-    path_cache: HashMap<PathBuf, NodeData>,
+    /// Index mapping every known path to its inode.
+    path_to_inode: HashMap<PathBuf, Inode>,
+
+    /// Index mapping a directory's inode to the inodes of its direct children.
+    children: HashMap<Inode, Vec<Inode>>,
+
+    /// Next inode to hand out to a directory synthesized for a missing ancestor component.
+    next_synthetic_inode: Inode,
+
+    /// Directory backing on-disk spillover for cached decompressed entries.
+    cache_dir: TempDir,
+
+    /// Cache of fully decompressed entry contents, keyed by inode.
+    content_cache: HashMap<Inode, CachedEntry>,
+
+    /// Inodes present in `content_cache`, ordered least- to most-recently-used.
+    cache_lru: VecDeque<Inode>,
+
+    /// Total bytes currently accounted for by `content_cache`.
+    cache_total_bytes: u64,
+
+    /// Extended attributes captured at creation time, keyed by entry path. Populated from the
+    /// `.xattrs` entry, if present, when the tree is built.
+    xattrs: HashMap<PathBuf, HashMap<String, Vec<u8>>>,
 }
 
 impl AppImageFileSystem {
@@ -72,12 +219,24 @@ impl AppImageFileSystem {
             Err(_) => return None,
         };
 
+        let cache_dir = match TempDir::new("appimagezip-cache") {
+            Ok(v) => v,
+            Err(_) => return None,
+        };
+
         Some(Self {
             metadata: metadata,
             ready: NotifyFlag::new(),
             archive: archive,
             inode_cache: HashMap::new(),
-            path_cache: HashMap::new(),
+            path_to_inode: HashMap::new(),
+            children: HashMap::new(),
+            next_synthetic_inode: 0,
+            cache_dir: cache_dir,
+            content_cache: HashMap::new(),
+            cache_lru: VecDeque::new(),
+            cache_total_bytes: 0,
+            xattrs: HashMap::new(),
         })
     }
 
@@ -95,98 +254,267 @@ impl AppImageFileSystem {
         self.archive.len() as u64 + 1
     }
 
-    fn get_node_by_inode(&mut self, inode: Inode) -> Option<NodeData> {
-        if inode < FUSE_ROOT_ID || inode > self.get_inode_count() {
-            return None;
+    /// Build the full directory tree in one pass over the archive. Called once from `init`.
+    ///
+    /// Every zip entry is visited exactly once and assigned an inode. Zip archives frequently
+    /// omit intermediate directory entries, so any ancestor component that isn't itself present
+    /// in the archive is synthesized as an empty directory node with an inode above the archive's
+    /// entry count.
+    fn build_tree(&mut self) {
+        let root = NodeData {
+            path: PathBuf::new(),
+            attr: FileAttr {
+                ino: FUSE_ROOT_ID,
+                size: 0,
+                blocks: 0,
+                atime: Timespec::new(self.metadata.atime(), self.metadata.atime_nsec() as i32),
+                mtime: Timespec::new(self.metadata.mtime(), self.metadata.mtime_nsec() as i32),
+                ctime: Timespec::new(self.metadata.ctime(), self.metadata.ctime_nsec() as i32),
+                crtime: Timespec::new(self.metadata.ctime(), self.metadata.ctime_nsec() as i32),
+                kind: FileType::Directory,
+                perm: self.metadata.permissions().mode() as u16,
+                nlink: 2,
+                uid: self.metadata.uid(),
+                gid: self.metadata.gid(),
+                rdev: 0,
+                flags: 0,
+            },
+            parent_inode: FUSE_ROOT_ID,
+            link_target: None,
+        };
+
+        self.inode_cache.insert(FUSE_ROOT_ID, root);
+        self.path_to_inode.insert(PathBuf::new(), FUSE_ROOT_ID);
+        self.children.insert(FUSE_ROOT_ID, Vec::new());
+        self.next_synthetic_inode = self.get_inode_count() + 1;
+
+        for index in 0..self.archive.len() {
+            let is_xattrs_entry = self.archive.by_index(index).unwrap().name() == XATTRS_ENTRY_NAME;
+
+            if is_xattrs_entry {
+                let mut entry = self.archive.by_index(index).unwrap();
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut data).unwrap();
+                self.xattrs = parse_xattrs(&data);
+            }
+        }
+
+        for index in 0..self.archive.len() {
+            let name = self.archive.by_index(index).unwrap().name().to_string();
+
+            // These are internal side channels (update tooling, captured xattrs), not part of
+            // the app's own filesystem view, so don't materialize them as nodes.
+            if name == UPDATE_INFORMATION_ENTRY_NAME || name == XATTRS_ENTRY_NAME {
+                continue;
+            }
+
+            let inode = index as u64 + 2;
+            let mut node = self.read_entry_node(index, inode);
+            let path = node.path.clone();
+            let parent_inode = self.ensure_dir(path.parent());
+
+            if let Some(&existing_inode) = self.path_to_inode.get(&path) {
+                // The archive already contained an explicit entry for a path we synthesized
+                // while processing one of its descendants. Keep that inode (it is already
+                // wired into the tree) and just refresh its metadata.
+                node.attr.ino = existing_inode;
+                node.parent_inode = parent_inode;
+                self.inode_cache.insert(existing_inode, node);
+            } else {
+                node.parent_inode = parent_inode;
+                self.inode_cache.insert(inode, node);
+                self.path_to_inode.insert(path, inode);
+                self.children.entry(parent_inode).or_insert_with(Vec::new).push(inode);
+                self.children.entry(inode).or_insert_with(Vec::new);
+            }
         }
+    }
+
+    /// Ensure that a directory node exists for `path`, synthesizing it and any missing ancestors
+    /// above it as needed. Returns the inode of the directory.
+    fn ensure_dir(&mut self, path: Option<&Path>) -> Inode {
+        let path = match path {
+            None => return FUSE_ROOT_ID,
+            Some(p) if p.as_os_str().is_empty() => return FUSE_ROOT_ID,
+            Some(p) => p,
+        };
 
-        if self.inode_cache.contains_key(&inode) {
-            return self.inode_cache.get(&inode).cloned();
+        if let Some(&inode) = self.path_to_inode.get(path) {
+            return inode;
         }
 
-        let node = if inode == FUSE_ROOT_ID {
-            NodeData {
-                path: PathBuf::new(),
-                is_dir: true,
-                attr: FileAttr {
-                    ino: 1,
-                    size: 0,
-                    blocks: 0,
-                    atime: Timespec::new(self.metadata.atime(), self.metadata.atime_nsec() as i32),
-                    mtime: Timespec::new(self.metadata.mtime(), self.metadata.mtime_nsec() as i32),
-                    ctime: Timespec::new(self.metadata.ctime(), self.metadata.ctime_nsec() as i32),
-                    crtime: Timespec::new(self.metadata.ctime(), self.metadata.ctime_nsec() as i32),
-                    kind: FileType::Directory,
-                    perm: self.metadata.permissions().mode() as u16,
-                    nlink: 2,
-                    uid: self.metadata.uid(),
-                    gid: self.metadata.gid(),
-                    rdev: 0,
-                    flags: 0,
+        let parent_inode = self.ensure_dir(path.parent());
+
+        let inode = self.next_synthetic_inode;
+        self.next_synthetic_inode += 1;
+
+        let time = Timespec::new(self.metadata.mtime(), self.metadata.mtime_nsec() as i32);
+        let node = NodeData {
+            path: path.to_path_buf(),
+            attr: FileAttr {
+                ino: inode,
+                size: 0,
+                blocks: 0,
+                atime: time,
+                mtime: time,
+                ctime: time,
+                crtime: time,
+                kind: FileType::Directory,
+                perm: self.metadata.permissions().mode() as u16,
+                nlink: 2,
+                uid: self.metadata.uid(),
+                gid: self.metadata.gid(),
+                rdev: 0,
+                flags: 0,
+            },
+            parent_inode: parent_inode,
+            link_target: None,
+        };
+
+        self.inode_cache.insert(inode, node);
+        self.path_to_inode.insert(path.to_path_buf(), inode);
+        self.children.entry(parent_inode).or_insert_with(Vec::new).push(inode);
+        self.children.entry(inode).or_insert_with(Vec::new);
+
+        inode
+    }
+
+    /// Read a single zip entry and turn it into a `NodeData`. `parent_inode` is left unset
+    /// (pointing at the root) and must be filled in by the caller once the parent is known.
+    fn read_entry_node(&mut self, index: usize, inode: Inode) -> NodeData {
+        let mut entry = self.archive.by_index(index).unwrap();
+        let time = entry.last_modified().to_timespec();
+
+        // Get the external attributes and derive the permissions from that.
+        let external_attributes_high = entry.unix_mode().unwrap_or(0o777);
+        let mode = external_attributes_high as u16 & 0o777;
+
+        // Determine the file type from the stored unix mode. Fall back to treating the entry as
+        // a directory if the name ends in / and no usable mode bits were stored.
+        let mut kind = file_type_from_mode(external_attributes_high);
+        if kind == FileType::RegularFile && entry.name().ends_with("/") {
+            kind = FileType::Directory;
+        }
+        let is_dir = kind == FileType::Directory;
+
+        // Symlinks store their target as the entry's (decompressed) contents. A corrupt entry
+        // shouldn't be able to take down the whole mount, so fall back to an empty, inert link
+        // rather than propagating the read error.
+        let (size, link_target) = if kind == FileType::Symlink {
+            let mut target = Vec::with_capacity(entry.size() as usize);
+            match entry.read_to_end(&mut target) {
+                Ok(_) => {
+                    let size = target.len() as u64;
+                    (size, Some(target))
                 },
+                Err(_) => (0, Some(Vec::new())),
             }
         } else {
-            let entry = self.archive.by_index(inode as usize - 2).unwrap();
-            let time = entry.last_modified().to_timespec();
-
-            // Get the external attributes and derive the permissions from that.
-            let external_attributes_high = entry.unix_mode().unwrap_or(0o777);
-            let mode = external_attributes_high as u16 & 0o777;
-
-            // Determine if the entry is a directory. If the name ends in /, then it is a directory. If bit 4 is set
-            // then it is also a directory.
-            let is_dir = external_attributes_high & libc::S_IFDIR == libc::S_IFDIR || entry.name().ends_with("/");
-
-            NodeData {
-                path: PathBuf::from(entry.name()),
-                is_dir: is_dir,
-                attr: FileAttr {
-                    ino: inode,
-                    size: entry.size(),
-                    blocks: 0,
-                    atime: time,
-                    mtime: time,
-                    ctime: time,
-                    crtime: time,
-                    kind: if is_dir {
-                        FileType::Directory
-                    } else {
-                        FileType::RegularFile
-                    },
-                    perm: mode,
-                    nlink: 3,
-                    uid: self.metadata.uid(),
-                    gid: self.metadata.gid(),
-                    rdev: 0,
-                    flags: 0,
-                },
-            }
+            (entry.size(), None)
         };
 
-        self.inode_cache.insert(inode, node.clone());
-        Some(node)
+        NodeData {
+            path: PathBuf::from(entry.name()),
+            attr: FileAttr {
+                ino: inode,
+                size: size,
+                blocks: 0,
+                atime: time,
+                mtime: time,
+                ctime: time,
+                crtime: time,
+                kind: kind,
+                perm: mode,
+                nlink: if is_dir { 2 } else { 1 },
+                uid: self.metadata.uid(),
+                gid: self.metadata.gid(),
+                rdev: 0,
+                flags: 0,
+            },
+            parent_inode: FUSE_ROOT_ID,
+            link_target: link_target,
+        }
+    }
+
+    fn get_node_by_inode(&self, inode: Inode) -> Option<NodeData> {
+        self.inode_cache.get(&inode).cloned()
     }
 
-    fn get_node_by_path(&mut self, path: PathBuf) -> Option<NodeData> {
-        if self.path_cache.contains_key(&path) {
-            return self.path_cache.get(&path).cloned();
+    fn get_node_by_path(&self, path: &Path) -> Option<NodeData> {
+        self.path_to_inode.get(path).and_then(|inode| self.inode_cache.get(inode).cloned())
+    }
+
+    /// Mark `inode` as the most-recently-used entry in the content cache.
+    fn touch_content_cache(&mut self, inode: Inode) {
+        if let Some(pos) = self.cache_lru.iter().position(|&i| i == inode) {
+            self.cache_lru.remove(pos);
         }
 
-        for i in 1..self.get_inode_count()+1 {
-            let node = self.get_node_by_inode(i).unwrap();
+        self.cache_lru.push_back(inode);
+    }
 
-            if node.path == path {
-                self.path_cache.insert(path, node.clone());
-                return Some(node);
+    /// Evict least-recently-used entries until the cache has room for `incoming_size` more bytes.
+    fn evict_content_cache(&mut self, incoming_size: u64) {
+        while self.cache_total_bytes + incoming_size > CONTENT_CACHE_CAP {
+            let oldest = match self.cache_lru.pop_front() {
+                Some(v) => v,
+                None => break,
+            };
+
+            if let Some(entry) = self.content_cache.remove(&oldest) {
+                self.cache_total_bytes -= entry.size;
             }
         }
+    }
+
+    /// Read `length` bytes at `offset` from the entry at `archive_index`, fully decompressing and
+    /// caching it on the first access so subsequent reads don't re-decompress from the start.
+    fn read_cached(&mut self, inode: Inode, archive_index: usize, total_size: u64, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        if !self.content_cache.contains_key(&inode) {
+            let mut entry = self.archive.by_index(archive_index).unwrap();
+            let mut data = Vec::with_capacity(total_size as usize);
+            entry.read_to_end(&mut data)?;
+
+            let cached_data = if total_size > MEMORY_CACHE_ENTRY_LIMIT {
+                let path = self.cache_dir.path().join(inode.to_string());
+                let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+                file.write_all(&data)?;
+                CacheData::Disk(file)
+            } else {
+                CacheData::Memory(data)
+            };
+
+            self.evict_content_cache(total_size);
+            self.content_cache.insert(inode, CachedEntry { data: cached_data, size: total_size });
+            self.cache_total_bytes += total_size;
+        }
 
-        None
+        self.touch_content_cache(inode);
+
+        let entry = self.content_cache.get(&inode).unwrap();
+        let end = cmp::min(offset + length as u64, entry.size);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+        let read_len = (end - offset) as usize;
+
+        match entry.data {
+            CacheData::Memory(ref data) => {
+                let start = offset as usize;
+                Ok(data[start..start + read_len].to_vec())
+            },
+            CacheData::Disk(ref file) => {
+                let mut buffer = vec![0u8; read_len];
+                file.read_at(&mut buffer, offset)?;
+                Ok(buffer)
+            },
+        }
     }
 }
 
 impl Filesystem for AppImageFileSystem {
     fn init(&mut self, _req: &Request) -> Result<(), i32> {
+        self.build_tree();
         self.ready.notify_all();
 
         println!("inode count: {}", self.get_inode_count());
@@ -198,7 +526,7 @@ impl Filesystem for AppImageFileSystem {
             let mut child_path = parent.path.clone();
             child_path.push(child_name);
 
-            if let Some(child) = self.get_node_by_path(child_path) {
+            if let Some(child) = self.get_node_by_path(&child_path) {
                 reply.entry(&TTL, &child.attr, 0);
                 return;
             }
@@ -218,8 +546,7 @@ impl Filesystem for AppImageFileSystem {
     }
 
     fn readdir(&mut self, _req: &Request, inode: u64, _fh: u64, offset: u64, mut reply: ReplyDirectory) {
-        if let Some(parent_node) = self.get_node_by_inode(inode) {
-            // println!("readdir({})", inode);
+        if let Some(node) = self.get_node_by_inode(inode) {
             if offset > 0 {
                 reply.ok();
                 return;
@@ -227,38 +554,16 @@ impl Filesystem for AppImageFileSystem {
 
             let mut reply_offset = 0;
 
-            // Add the current directory.
+            // Add the current and parent directory entries.
             reply.add(inode, reply_offset, FileType::Directory, ".");
             reply_offset += 1;
+            reply.add(node.parent_inode, reply_offset, FileType::Directory, "..");
+            reply_offset += 1;
 
-            // Find the parent directory.
-            if inode == FUSE_ROOT_ID {
-                reply.add(1, reply_offset, FileType::Directory, "..");
-                reply_offset += 1;
-            } else if let Some(parent_parent_path) = parent_node.path.parent() {
-                for i in 1..self.get_inode_count()+1 {
-                    let node = self.get_node_by_inode(i).unwrap();
-
-                    if node.path == parent_parent_path {
-                        reply.add(node.inode(), reply_offset, FileType::Directory, "..");
-                        reply_offset += 1;
-                        break;
-                    }
-                }
-            }
-
-            // Find child nodes.
-            for i in 2..self.get_inode_count()+1 {
-                let child_node = self.get_node_by_inode(i).unwrap();
-
-                if let Some(child_parent_path) = child_node.path.parent() {
-                    // println!("{:?} == {:?}?", child_parent_path, parent_node.path);
-                    if child_parent_path == parent_node.path {
-                        println!("{:?} > {} - {:?}", parent_node.path, child_node.inode(), child_node.path);
-                        reply.add(child_node.inode(), reply_offset, child_node.attr.kind, child_node.name());
-                        reply_offset += 1;
-                    }
-                } else if inode == FUSE_ROOT_ID {
+            // Child nodes are already indexed, so this is just an iteration over the cached list.
+            if let Some(children) = self.children.get(&inode).cloned() {
+                for child_inode in children {
+                    let child_node = self.get_node_by_inode(child_inode).unwrap();
                     reply.add(child_node.inode(), reply_offset, child_node.attr.kind, child_node.name());
                     reply_offset += 1;
                 }
@@ -270,29 +575,104 @@ impl Filesystem for AppImageFileSystem {
         }
     }
 
+    fn readlink(&mut self, _req: &Request, inode: u64, reply: ReplyData) {
+        if let Some(data) = self.get_node_by_inode(inode) {
+            if let Some(target) = data.link_target {
+                reply.data(&target);
+                return;
+            }
+        }
+
+        reply.error(libc::EINVAL);
+    }
+
     fn read(&mut self, _req: &Request, inode: u64, _fh: u64, offset: u64, size: u32, reply: ReplyData) {
         if let Some(data) = self.get_node_by_inode(inode) {
-            if !data.is_dir {
-                let mut entry = self.archive.by_index(inode as usize - 2).unwrap();
+            if data.attr.kind == FileType::RegularFile {
+                let archive_index = inode as usize - 2;
 
-                let mut read = offset as usize + size as usize;
-                if read > data.attr.size as usize {
-                    read = data.attr.size as usize;
+                match self.read_cached(inode, archive_index, data.attr.size, offset, size as usize) {
+                    Ok(buffer) => reply.data(&buffer),
+                    Err(e) => reply.error(e.raw_os_error().unwrap_or(libc::EIO)),
                 }
 
-                let mut buffer = Vec::with_capacity(read);
-                buffer.resize(read, 0);
+                return;
+            }
+        }
+
+        reply.error(libc::ENOENT);
+    }
+
+    fn statfs(&mut self, _req: &Request, _inode: u64, reply: ReplyStatfs) {
+        const BLOCK_SIZE: u32 = 512;
+
+        let total_size: u64 = self.inode_cache.values()
+            .filter(|node| node.attr.kind != FileType::Directory)
+            .map(|node| node.attr.size)
+            .sum();
+
+        let blocks = (total_size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
+
+        // `get_inode_count` is sized off the archive's entry count alone; `build_tree` also
+        // synthesizes extra directory nodes for unlisted intermediate directories, so the real
+        // total is however many nodes ended up in `inode_cache`.
+        reply.statfs(blocks, 0, 0, self.inode_cache.len() as u64, 0, BLOCK_SIZE, 255, BLOCK_SIZE);
+    }
+
+    fn getxattr(&mut self, _req: &Request, inode: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        let node = match self.get_node_by_inode(inode) {
+            Some(v) => v,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            },
+        };
+
+        let name = match name.to_str() {
+            Some(v) => v,
+            None => {
+                reply.error(libc::ENODATA);
+                return;
+            },
+        };
 
-                if let Err(e) = entry.read_exact(&mut buffer) {
-                    reply.error(e.raw_os_error().unwrap_or(libc::EIO));
-                    return;
+        match self.xattrs.get(&node.path).and_then(|attrs| attrs.get(name)) {
+            Some(value) => {
+                if size == 0 {
+                    reply.size(value.len() as u32);
+                } else if value.len() > size as usize {
+                    reply.error(libc::ERANGE);
+                } else {
+                    reply.data(value);
                 }
+            },
+            None => reply.error(libc::ENODATA),
+        }
+    }
 
-                reply.data(&buffer[offset as usize..]);
+    fn listxattr(&mut self, _req: &Request, inode: u64, size: u32, reply: ReplyXattr) {
+        let node = match self.get_node_by_inode(inode) {
+            Some(v) => v,
+            None => {
+                reply.error(libc::ENOENT);
                 return;
+            },
+        };
+
+        let mut names = Vec::new();
+        if let Some(attrs) = self.xattrs.get(&node.path) {
+            for name in attrs.keys() {
+                names.extend_from_slice(name.as_bytes());
+                names.push(0);
             }
         }
 
-        reply.error(libc::ENOENT);
+        if size == 0 {
+            reply.size(names.len() as u32);
+        } else if names.len() > size as usize {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(&names);
+        }
     }
 }
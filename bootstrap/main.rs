@@ -11,9 +11,14 @@ mod fs;
 
 use fs::AppImageFileSystem;
 use std::env;
-use std::fs::read_link;
+use std::fs::{create_dir_all, read_link, remove_file, File};
+use std::io;
+use std::io::Read;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
 use std::process::{exit, Command};
 use tempdir::TempDir;
+use zip::ZipArchive;
 
 
 macro_rules! printerr {
@@ -27,7 +32,155 @@ macro_rules! printerr {
     };
 }
 
-fn run() -> i32 {
+/// Print the archive's file tree (name, size, mode) straight from the zip metadata, without
+/// mounting or extracting anything.
+fn list_archive() -> i32 {
+    let file = match File::open("/proc/self/exe") {
+        Ok(v) => v,
+        Err(_) => {
+            printerr!("Cannot read AppImage archive, binary could be corrupt.");
+            return 70;
+        },
+    };
+
+    let mut archive = match ZipArchive::new(file) {
+        Ok(v) => v,
+        Err(_) => {
+            printerr!("Cannot read AppImage archive, binary could be corrupt.");
+            return 70;
+        },
+    };
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).unwrap();
+        println!("{:o} {:>10} {}", entry.unix_mode().unwrap_or(0o644), entry.size(), entry.name());
+    }
+
+    0
+}
+
+/// The name of the zip entry holding the encoded update endpoint; it's an internal side channel
+/// for update tooling, not part of the app's own filesystem view, so it's skipped on extraction.
+/// See `fs.rs`'s constant of the same name.
+const UPDATE_INFORMATION_ENTRY_NAME: &'static str = ".update_information";
+
+/// The name of the zip entry holding captured extended attributes; like
+/// `UPDATE_INFORMATION_ENTRY_NAME`, it's an internal side channel, not app content, so it's
+/// skipped on extraction. See `fs.rs`'s constant of the same name.
+const XATTRS_ENTRY_NAME: &'static str = ".xattrs";
+
+/// Resolve an untrusted zip entry name against `dest`, rejecting anything that would escape it
+/// (a `..` component or an absolute path) instead of joining it blindly. AppImages are routinely
+/// run from archives nobody but their publisher controls, so a crafted entry name can't be
+/// allowed to write outside the extraction directory.
+fn safe_extract_path(dest: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut out_path = dest.to_path_buf();
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => out_path.push(part),
+            Component::CurDir => {},
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("zip entry {:?} escapes the extraction directory", name),
+                ));
+            },
+        }
+    }
+
+    Ok(out_path)
+}
+
+/// Recreate the contents of the embedded zip archive under `dest`, preserving symlinks and the
+/// unix modes of regular files. Directories are created with default permissions; their stored
+/// modes are not applied.
+fn extract_archive(dest: &Path) -> io::Result<()> {
+    let file = File::open("/proc/self/exe")?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "corrupt AppImage archive"))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).unwrap();
+
+        if entry.name() == UPDATE_INFORMATION_ENTRY_NAME || entry.name() == XATTRS_ENTRY_NAME {
+            continue;
+        }
+
+        let mode = entry.unix_mode().unwrap_or(0o755);
+        let out_path = safe_extract_path(dest, entry.name())?;
+
+        if let Some(parent) = out_path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        if entry.name().ends_with('/') || mode & libc::S_IFDIR == libc::S_IFDIR {
+            create_dir_all(&out_path)?;
+        } else if mode & libc::S_IFLNK == libc::S_IFLNK {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+
+            // The entry may have been visited already if the archive lists it twice.
+            let _ = remove_file(&out_path);
+            symlink(target, &out_path)?;
+        } else {
+            let mut out_file = File::create(&out_path)?;
+            io::copy(&mut entry, &mut out_file)?;
+
+            let mut permissions = out_file.metadata()?.permissions();
+            permissions.set_mode(mode);
+            out_file.set_permissions(permissions)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract the AppImage to a temporary directory, run its `AppRun`, and clean up on exit.
+fn run_extracted() -> i32 {
+    let extract_dir = match TempDir::new("appimage") {
+        Ok(v) => v,
+        Err(_) => {
+            printerr!("Failed to create extraction directory.");
+            return 75;
+        },
+    };
+
+    if let Err(e) = extract_archive(extract_dir.path()) {
+        printerr!("Failed to extract AppImage: {}", e);
+        return 70;
+    }
+
+    run_app_dir(extract_dir.path())
+}
+
+/// This process's own control flags, consumed by `run()` and never meant to reach the wrapped
+/// app's `AppRun`.
+const APPIMAGE_FLAG_PREFIX: &'static str = "--appimage-";
+
+/// Set up the AppImage environment variables and exec `AppRun` out of `app_dir`.
+fn run_app_dir(app_dir: &Path) -> i32 {
+    env::set_var("APPIMAGE", read_link("/proc/self/exe").unwrap());
+    env::set_var("APPDIR", app_dir);
+
+    let app_run_path = app_dir.join("AppRun");
+
+    // Strip our own --appimage-* flags out before forwarding argv, the same way real AppImage
+    // runtimes consume their own options before exec'ing the payload.
+    let args = env::args().filter(|a| !a.starts_with(APPIMAGE_FLAG_PREFIX));
+
+    match Command::new(&app_run_path).args(args).status() {
+        Ok(status) => status.code().unwrap_or(0),
+        Err(e) => {
+            printerr!("Failed to execute {:?}: {}", app_run_path, e);
+            70
+        },
+    }
+}
+
+/// Mount the AppImage as a FUSE file system and run its `AppRun`. Falls back to extract-and-run
+/// if the file system cannot be mounted (e.g. no FUSE support on this host).
+fn run_mounted() -> i32 {
     // Open this binary as an AppImage file system.
     let file_system = match AppImageFileSystem::open_self() {
         Some(v) => v,
@@ -54,32 +207,50 @@ fn run() -> i32 {
         match fuse::spawn_mount(file_system, &mount_path, &[]) {
             Ok(s) => s,
             Err(e) => {
-                printerr!("Failed to mount FUSE file system: {}", e);
-                return 71;
+                printerr!("Failed to mount FUSE file system ({}), falling back to extract-and-run.", e);
+                return run_extracted();
             },
         }
     };
 
-    // Some useful variable for the client application.
-    env::set_var("APPIMAGE", read_link("/proc/self/exe").unwrap());
-    env::set_var("APPDIR", mount_dir.path());
-
-    let mut app_run_path = mount_dir.path().to_path_buf();
-    app_run_path.push("AppRun");
-
     // Wait for the file system to be initialized.
     ready.wait();
 
-    // Run the client application.
-    if let Err(e) = Command::new(&app_run_path).args(env::args()).status() {
-        printerr!("Failed to execute {:?}: {}", app_run_path, e);
-        return 70;
-    }
+    let status = run_app_dir(&mount_path);
 
     drop(session);
     drop(mount_dir);
 
-    0
+    status
+}
+
+fn run() -> i32 {
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|a| a == "--appimage-list") {
+        return list_archive();
+    }
+
+    if args.iter().any(|a| a == "--appimage-extract") {
+        let dest = Path::new("appimage-extracted");
+
+        return match extract_archive(dest) {
+            Ok(_) => {
+                println!("{}", dest.display());
+                0
+            },
+            Err(e) => {
+                printerr!("Failed to extract AppImage: {}", e);
+                70
+            },
+        };
+    }
+
+    if args.iter().any(|a| a == "--appimage-extract-and-run") {
+        return run_extracted();
+    }
+
+    run_mounted()
 }
 
 fn main() {
@@ -1,14 +1,115 @@
 use bootstrap;
+use libc;
+use std::ffi::CString;
 use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::*;
 use std::path::{Path, PathBuf};
+use std::ptr;
+use std::str;
 use time::{self, Timespec};
 use util::RecursiveDirIterator;
 use zip::write::*;
 
 
+/// The name of the zip entry holding the encoded update endpoint.
+///
+/// The entry contains nothing but the UTF-8 bytes of the endpoint's `zsync|URL` or
+/// `bintray-zsync|user|repo|package|path` encoding (see `UpdateEndpoint::to_string`). Update
+/// tooling can locate it through the zip central directory and read just this entry without
+/// extracting or mounting the rest of the AppImage.
+const UPDATE_INFORMATION_ENTRY_NAME: &'static str = ".update_information";
+
+/// The name of the zip entry holding captured extended attributes for the other entries.
+///
+/// Its contents are a flat, little-endian encoded list of records, one per entry that had any
+/// xattrs set:
+///
+/// ```text
+/// u32             record count
+/// for each record:
+///     u32         entry name length, in bytes
+///     [u8]        entry name (matches the corresponding entry's name exactly, e.g. "foo/bar" or
+///                 "foo/" for a directory)
+///     u32         xattr count
+///     for each xattr:
+///         u32     name length, in bytes
+///         [u8]    name (UTF-8)
+///         u32     value length, in bytes
+///         [u8]    value (raw bytes)
+/// ```
+const XATTRS_ENTRY_NAME: &'static str = ".xattrs";
+
+/// Append a little-endian `u32` to `buf`.
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.push((value & 0xff) as u8);
+    buf.push(((value >> 8) & 0xff) as u8);
+    buf.push(((value >> 16) & 0xff) as u8);
+    buf.push(((value >> 24) & 0xff) as u8);
+}
+
+/// Append a length-prefixed byte string to `buf`.
+fn push_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    push_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+/// Read the extended attributes set on `path` via the unix xattr APIs.
+fn read_xattrs(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let list_size = unsafe { libc::listxattr(c_path.as_ptr(), ptr::null_mut(), 0) };
+    if list_size <= 0 {
+        return Vec::new();
+    }
+
+    let mut list_buf = vec![0u8; list_size as usize];
+    let list_size = unsafe {
+        libc::listxattr(c_path.as_ptr(), list_buf.as_mut_ptr() as *mut libc::c_char, list_buf.len())
+    };
+    if list_size <= 0 {
+        return Vec::new();
+    }
+    list_buf.truncate(list_size as usize);
+
+    let mut xattrs = Vec::new();
+
+    for name in list_buf.split(|&b| b == 0).filter(|s| !s.is_empty()) {
+        let name_str = match str::from_utf8(name) {
+            Ok(v) => v.to_owned(),
+            Err(_) => continue,
+        };
+
+        let c_name = match CString::new(name) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let value_size = unsafe { libc::getxattr(c_path.as_ptr(), c_name.as_ptr(), ptr::null_mut(), 0) };
+        if value_size < 0 {
+            continue;
+        }
+
+        let mut value_buf = vec![0u8; value_size as usize];
+        let value_size = unsafe {
+            libc::getxattr(c_path.as_ptr(), c_name.as_ptr(), value_buf.as_mut_ptr() as *mut libc::c_void, value_buf.len())
+        };
+        if value_size < 0 {
+            continue;
+        }
+        value_buf.truncate(value_size as usize);
+
+        xattrs.push((name_str, value_buf));
+    }
+
+    xattrs
+}
+
 /// Update metadata information.
 #[derive(Clone)]
 pub enum UpdateEndpoint {
@@ -24,6 +125,38 @@ pub enum UpdateEndpoint {
     },
 }
 
+impl UpdateEndpoint {
+    /// Parse an update endpoint from its `zsync|URL` or `bintray-zsync|user|repo|package|path`
+    /// encoding, as produced by `ToString`.
+    pub fn parse(s: &str) -> Option<UpdateEndpoint> {
+        let mut parts = s.split('|');
+
+        match parts.next() {
+            Some("zsync") => {
+                let url = parts.next()?;
+
+                Some(UpdateEndpoint::Zsync {
+                    url: url.to_owned(),
+                })
+            },
+            Some("bintray-zsync") => {
+                let username = parts.next()?;
+                let repository = parts.next()?;
+                let package = parts.next()?;
+                let path = parts.next()?;
+
+                Some(UpdateEndpoint::BintrayZsync {
+                    username: username.to_owned(),
+                    repository: repository.to_owned(),
+                    package: package.to_owned(),
+                    path: path.to_owned(),
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
 impl ToString for UpdateEndpoint {
     fn to_string(&self) -> String {
         match self {
@@ -54,12 +187,19 @@ impl Creator {
         }
     }
 
+    /// Set the update endpoint to embed in the produced AppImage.
+    pub fn set_update_endpoint(&mut self, update_endpoint: UpdateEndpoint) {
+        self.update_endpoint = Some(update_endpoint);
+    }
+
     pub fn write_to<W: Write + Seek>(&self, mut writer: W) -> io::Result<()> {
         // First start the file with the bootstrap binary.
         bootstrap::write(&mut writer);
 
         // Now create a zip archive by copying all files in the app dir.
         let mut zip = ZipWriter::new(&mut writer);
+        let mut xattr_records: Vec<u8> = Vec::new();
+        let mut xattr_entry_count: u32 = 0;
 
         for entry in RecursiveDirIterator::new(&self.app_dir)?.filter_map(|r| r.ok()) {
             println!("copy: {:?}", entry.path());
@@ -73,11 +213,27 @@ impl Creator {
                     .last_modified_time(time::at(mtime))
                     .unix_permissions(metadata.mode());
 
+                let name = if entry.file_type()?.is_dir() {
+                    format!("{}/", relative_path.to_string_lossy())
+                } else {
+                    relative_path.to_string_lossy().into_owned()
+                };
+
+                let xattrs = read_xattrs(&path);
+                if !xattrs.is_empty() {
+                    push_bytes(&mut xattr_records, name.as_bytes());
+                    push_u32(&mut xattr_records, xattrs.len() as u32);
+                    for (xattr_name, xattr_value) in &xattrs {
+                        push_bytes(&mut xattr_records, xattr_name.as_bytes());
+                        push_bytes(&mut xattr_records, xattr_value);
+                    }
+                    xattr_entry_count += 1;
+                }
+
                 if entry.file_type()?.is_dir() {
-                    let name_with_slash = format!("{}/", relative_path.to_string_lossy());
-                    zip.add_directory(name_with_slash, options)?;
+                    zip.add_directory(name, options)?;
                 } else {
-                    zip.start_file(relative_path.to_string_lossy(), options)?;
+                    zip.start_file(name, options)?;
 
                     let mut file = File::open(entry.path())?;
                     io::copy(&mut file, &mut zip)?;
@@ -86,6 +242,25 @@ impl Creator {
             }
         }
 
+        // Embed the update endpoint, if any, as its own entry so updater tooling can locate it
+        // through the zip central directory.
+        if let Some(ref update_endpoint) = self.update_endpoint {
+            zip.start_file(UPDATE_INFORMATION_ENTRY_NAME, FileOptions::default())?;
+            zip.write_all(update_endpoint.to_string().as_bytes())?;
+            zip.flush()?;
+        }
+
+        // Embed any captured extended attributes as their own entry, see `XATTRS_ENTRY_NAME`.
+        if xattr_entry_count > 0 {
+            let mut xattrs_blob = Vec::new();
+            push_u32(&mut xattrs_blob, xattr_entry_count);
+            xattrs_blob.extend_from_slice(&xattr_records);
+
+            zip.start_file(XATTRS_ENTRY_NAME, FileOptions::default())?;
+            zip.write_all(&xattrs_blob)?;
+            zip.flush()?;
+        }
+
         zip.finish()?;
 
         Ok(())
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 extern crate getopts;
+extern crate libc;
 extern crate time;
 extern crate zip;
 
@@ -7,9 +8,11 @@ mod appimage;
 mod bootstrap;
 mod util;
 
+use appimage::UpdateEndpoint;
 use getopts::Options;
 use std::env;
 use std::io::stdout;
+use std::process::exit;
 
 
 fn print_help(options: &Options) {
@@ -21,6 +24,8 @@ fn main() {
 
     options.optflag("h", "help", "Show this help message");
     options.optopt("o", "output", "Write the AppImage to FILE", "FILE");
+    options.optopt("u", "updateinformation", "Embed update information, as \
+        'zsync|URL' or 'bintray-zsync|USER|REPO|PACKAGE|PATH'", "STRING");
     options.optopt("", "target", "Build for the target triple", "TRIPLE");
     options.optflag("D", "dump-bootstrap", "Dump the runtime bootstrap binary");
     options.optflag("v", "version", "Show version info");
@@ -46,7 +51,17 @@ fn main() {
 
     let app_dir = args.free.get(1);
     if let Some(app_dir) = app_dir {
-        let creator = appimage::Creator::new(app_dir);
+        let mut creator = appimage::Creator::new(app_dir);
+
+        if let Some(update_information) = args.opt_str("u") {
+            match UpdateEndpoint::parse(&update_information) {
+                Some(update_endpoint) => creator.set_update_endpoint(update_endpoint),
+                None => {
+                    println!("Error: invalid update information string: {:?}", update_information);
+                    exit(1);
+                },
+            }
+        }
 
         match creator.write_to_file(&output_file) {
             Ok(_) => {